@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rodio::Source;
+
+/// An audio source decoded via `ffmpeg_next` instead of `rodio::Decoder`,
+/// covering formats rodio doesn't (AAC, Opus, FLAC, video-container audio
+/// tracks, ...) and streaming PCM out in chunks rather than decoding the
+/// whole file up front. Resamples to mono `target_sample_rate` internally so
+/// it can feed the same `SourceTap`/`PcmBuffers` pipeline a `rodio::Decoder`
+/// does.
+pub struct FfmpegSource {
+    input: ffmpeg_next::format::context::Input,
+    decoder: ffmpeg_next::codec::decoder::Audio,
+    resampler: ffmpeg_next::software::resampling::Context,
+    stream_index: usize,
+    sample_rate: u32,
+    pending: VecDeque<i16>,
+    /// Set once `send_eof`'s drain has run, so it only happens once instead
+    /// of on every subsequent `decode_more` call after the container's
+    /// exhausted.
+    eof_flushed: bool,
+}
+
+impl FfmpegSource {
+    pub fn open(path: &str, target_sample_rate: u32) -> Result<Self, ffmpeg_next::Error> {
+        ffmpeg_next::init()?;
+
+        let input = ffmpeg_next::format::input(&path)?;
+        let stream = input
+            .streams()
+            .best(ffmpeg_next::media::Type::Audio)
+            .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+        let stream_index = stream.index();
+
+        let context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())?;
+        let decoder = context.decoder().audio()?;
+
+        let resampler = ffmpeg_next::software::resampler(
+            (decoder.format(), decoder.channel_layout(), decoder.rate()),
+            (
+                ffmpeg_next::format::Sample::I16(ffmpeg_next::format::sample::Type::Packed),
+                ffmpeg_next::channel_layout::ChannelLayout::MONO,
+                target_sample_rate,
+            ),
+        )?;
+
+        Ok(Self {
+            input,
+            decoder,
+            resampler,
+            stream_index,
+            sample_rate: target_sample_rate,
+            pending: VecDeque::new(),
+            eof_flushed: false,
+        })
+    }
+
+    /// Decodes and resamples as many packets belonging to our audio stream
+    /// as it takes to queue at least one more sample into `pending`. Once
+    /// the container is exhausted this flushes the decoder and resampler
+    /// exactly once (so their last buffered frame isn't silently dropped),
+    /// and returns `false` from then on.
+    fn decode_more(&mut self) -> bool {
+        // Takes one packet at a time so the borrow of `self.input` ends
+        // before `drain_decoder` borrows all of `self`.
+        loop {
+            let (index, packet) = match self.input.packets().next() {
+                Some((stream, packet)) => (stream.index(), packet),
+                None => break,
+            };
+            if index != self.stream_index {
+                continue;
+            }
+            if self.decoder.send_packet(&packet).is_err() {
+                continue;
+            }
+            self.drain_decoder();
+            if !self.pending.is_empty() {
+                return true;
+            }
+        }
+
+        if !self.eof_flushed {
+            self.eof_flushed = true;
+            let _ = self.decoder.send_eof();
+            self.drain_decoder();
+
+            // Flush whatever the resampler itself is still holding onto.
+            let empty = ffmpeg_next::frame::Audio::empty();
+            let mut resampled = ffmpeg_next::frame::Audio::empty();
+            if self.resampler.run(&empty, &mut resampled).is_ok() {
+                self.pending.extend(resampled.plane::<i16>(0).iter().copied());
+            }
+        }
+
+        !self.pending.is_empty()
+    }
+
+    /// Pulls every frame the decoder currently has buffered and resamples
+    /// each into `pending`. Must be drained to completion before the next
+    /// packet is sent — a single packet can hold more than one frame, and
+    /// sending another packet before draining this one gets silently
+    /// rejected by `send_packet`.
+    fn drain_decoder(&mut self) {
+        let mut decoded = ffmpeg_next::frame::Audio::empty();
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            let mut resampled = ffmpeg_next::frame::Audio::empty();
+            if self.resampler.run(&decoded, &mut resampled).is_ok() {
+                self.pending.extend(resampled.plane::<i16>(0).iter().copied());
+            }
+        }
+    }
+}
+
+impl Iterator for FfmpegSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        loop {
+            if let Some(sample) = self.pending.pop_front() {
+                return Some(sample);
+            }
+            if !self.decode_more() {
+                return None;
+            }
+        }
+    }
+}
+
+impl Source for FfmpegSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
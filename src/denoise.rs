@@ -0,0 +1,62 @@
+/// RNNoise processes audio in fixed 480-sample frames (10ms at 48kHz).
+pub const FRAME_SIZE: usize = 480;
+
+/// Runs interleaved PCM through an RNNoise denoiser (via `nnnoiseless`) one
+/// `DenoiseState` per channel, so the spectrum analyzer can react to actual
+/// signal instead of background hiss. Leftover samples that don't fill a
+/// full frame yet are carried over to the next `process` call, and each
+/// fully-processed frame also yields a voice-activity probability used to
+/// gate particle growth during silence.
+pub struct Denoiser {
+    channels: usize,
+    states: Vec<Box<nnnoiseless::DenoiseState<'static>>>,
+    pending: Vec<Vec<f32>>,
+    last_vad_probability: f32,
+}
+
+impl Denoiser {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            states: (0..channels).map(|_| nnnoiseless::DenoiseState::new()).collect(),
+            pending: vec![Vec::new(); channels],
+            last_vad_probability: 1.0,
+        }
+    }
+
+    /// De-interleaves `samples` by channel, denoises every complete
+    /// `FRAME_SIZE` frame available, and re-interleaves the result. Returns
+    /// the cleaned samples (which may trail the input by up to
+    /// `FRAME_SIZE - 1` samples per channel) and the most recent frame's VAD
+    /// probability.
+    pub fn process(&mut self, samples: &[f32]) -> (Vec<f32>, f32) {
+        for (i, &sample) in samples.iter().enumerate() {
+            self.pending[i % self.channels].push(sample);
+        }
+
+        let frames_ready = self.pending.iter().map(|p| p.len() / FRAME_SIZE).min().unwrap_or(0);
+        let mut cleaned_channels: Vec<Vec<f32>> = vec![Vec::with_capacity(frames_ready * FRAME_SIZE); self.channels];
+        for frame in 0..frames_ready {
+            let start = frame * FRAME_SIZE;
+            for channel in 0..self.channels {
+                let mut frame_out = [0f32; FRAME_SIZE];
+                self.last_vad_probability = self.states[channel]
+                    .process_frame(&mut frame_out, &self.pending[channel][start..start + FRAME_SIZE]);
+                cleaned_channels[channel].extend_from_slice(&frame_out);
+            }
+        }
+
+        let consumed = frames_ready * FRAME_SIZE;
+        for pending in &mut self.pending {
+            pending.drain(0..consumed);
+        }
+
+        let mut cleaned = Vec::with_capacity(consumed * self.channels);
+        for i in 0..consumed {
+            for channel in &cleaned_channels {
+                cleaned.push(channel[i]);
+            }
+        }
+        (cleaned, self.last_vad_probability)
+    }
+}
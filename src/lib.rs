@@ -1,3 +1,18 @@
+mod live_input;
+pub use live_input::*;
+mod pcm_buffer;
+pub use pcm_buffer::*;
+mod tone_source;
+pub use tone_source::*;
+mod denoise;
+pub use denoise::*;
+mod mixer;
+pub use mixer::*;
+#[cfg(feature = "ffmpeg")]
+mod decoder;
+#[cfg(feature = "ffmpeg")]
+pub use decoder::*;
+
 #[derive(Debug)]
 pub struct Particle {
     pub x: f32,
@@ -0,0 +1,89 @@
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::SampleFormat;
+
+use crate::PcmBuffers;
+
+/// Largest chunk a single capture callback is expected to push. A larger
+/// chunk is still pushed whole; it only triggers a warning.
+const CAPTURE_CHUNK_CAP: usize = 4096;
+
+/// Downmixes one interleaved capture callback's frames to mono and pushes
+/// them into `pcm`, warning if a single callback produced an unexpectedly
+/// large chunk.
+fn produce_mono(pcm: &Mutex<PcmBuffers>, mono: Vec<f32>) {
+    if mono.len() > CAPTURE_CHUNK_CAP {
+        eprintln!("Live capture chunk larger than expected; visualizer may lag.");
+    }
+    pcm.lock().unwrap().produce(mono);
+}
+
+/// Opens the system's default input device and starts streaming mono-downmixed
+/// samples into a freshly created `PcmBuffers`, the same producer/consumer
+/// buffer a decoded file or tone is tapped into. The returned `cpal::Stream`
+/// must be kept alive for the duration of capture (dropping it stops the
+/// stream), and the returned sample rate is whatever the device actually
+/// reports, which may not match `SAMPLE_COUNT`'s assumptions.
+pub fn start_live_capture() -> (cpal::Stream, Arc<Mutex<PcmBuffers>>, u32) {
+    let host = cpal::default_host();
+    let device = host
+        .default_input_device()
+        .expect("No default input device available");
+    let config = device
+        .default_input_config()
+        .expect("Failed to get default input config");
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+    let sample_format = config.sample_format();
+
+    let pcm = Arc::new(Mutex::new(PcmBuffers::new()));
+    let callback_pcm = pcm.clone();
+    let err_fn = |err| eprintln!("Live input stream error: {}", err);
+
+    // The device's default input format isn't always f32 (ALSA's default
+    // input config is commonly i16 or u16), and build_input_stream errors
+    // out if the callback's sample type doesn't match it.
+    let stream = match sample_format {
+        SampleFormat::F32 => device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let mono: Vec<f32> = data
+                    .chunks(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                    .collect();
+                produce_mono(&callback_pcm, mono);
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::I16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                let mono: Vec<f32> = data
+                    .chunks(channels)
+                    .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / channels as f32)
+                    .collect();
+                produce_mono(&callback_pcm, mono);
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::U16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                let mono: Vec<f32> = data
+                    .chunks(channels)
+                    .map(|frame| frame.iter().map(|&s| s as f32 - 32768.0).sum::<f32>() / channels as f32)
+                    .collect();
+                produce_mono(&callback_pcm, mono);
+            },
+            err_fn,
+            None,
+        ),
+        other => panic!("Unsupported input sample format: {:?}", other),
+    }.expect("Failed to build input stream");
+    stream.play().expect("Failed to start input stream");
+
+    (stream, pcm, sample_rate)
+}
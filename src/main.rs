@@ -1,6 +1,8 @@
 use winit::{
     event::{
+        ElementState,
         Event,
+        VirtualKeyCode,
         WindowEvent,
     },
     event_loop::ControlFlow,
@@ -12,6 +14,7 @@ use bui::{
     ellipse::*,
 };
 use std::{
+    sync::{Arc, Mutex},
     thread,
     time::{
         Duration,
@@ -38,6 +41,7 @@ constrainer::create_constrainer!(Constrainer {
     dynamic resx f32
     dynamic resy f32
     dynamic particles ParticleVec
+    dynamic vad_threshold f32
     external ellipse_renderer EllipseRendererRef
     external renderer RendererRef
     external resolution_buffer ResolutionBufferRef
@@ -77,19 +81,32 @@ constrainer::create_constrainer!(Constrainer {
     opgenset (resx, resy)
 });
 
+/// Cap for quiet bins: a diameter below it is scaled up tenfold and then
+/// capped at it, so quiet particles can still end up smaller. It is also what
+/// every particle is pinned to when `vad_probability` falls below
+/// `vad_threshold`, so silence
+/// (or denoised-out background noise) produces a still field instead of
+/// jitter from FFT noise floor.
+const FLOOR_DIAMETER: f32 = 0.001;
+
 // This function should be able to be generated automatically. Add feature to Constrainer for it.
 impl Constrainer {
-    pub fn set_particles_with_spectrum_and_deltatime(&mut self, spectrum: FrequencySpectrum, deltatime: f32, ellipse_renderer: EllipseRendererRef, renderer: RendererRef) {
+    pub fn set_particles_with_spectrum_and_deltatime(&mut self, spectrum: FrequencySpectrum, deltatime: f32, vad_probability: f32, ellipse_renderer: EllipseRendererRef, renderer: RendererRef) {
+        let silent = vad_probability < self.vad_threshold;
         let spectrum_data = spectrum.data();
         for (i, particle) in &mut self.particles.iter_mut().enumerate() {
-            let mut diameter = spectrum_data[i].1.val()*0.0001;
+            let mut diameter = if silent {
+                FLOOR_DIAMETER
+            } else {
+                spectrum_data[i].1.val()*0.0001
+            };
             if diameter > 0.02 {
                 diameter = 0.02+(diameter-0.02)/50.0;
             }
-            if diameter < 0.001 {
+            if diameter < FLOOR_DIAMETER {
                 diameter = diameter*10.0;
-                if diameter > 0.001 {
-                    diameter = 0.001;
+                if diameter > FLOOR_DIAMETER {
+                    diameter = FLOOR_DIAMETER;
                 }
             }
             particle.diameter = diameter;
@@ -103,15 +120,190 @@ impl Constrainer {
     }
 }
 
-fn main() {
-    let audio_file_name = if let Some(name) = std::env::args().nth(1) {
-        name
-    } else {
-        println!("Defaulting to ./input.mp3");
-        "input.mp3".to_string()
+/// Keeps a registered source's playback machinery alive. Tone/file sources
+/// play through the `AudioMixer`'s single shared output sink and need
+/// nothing kept alive once they're registered; live capture still owns its
+/// own cpal stream, which must stay alive for the duration of capture.
+enum PlaybackHandle {
+    Mixed,
+    Live(cpal::Stream),
+}
+
+/// Common rate the mixer resamples every source to before mixing.
+const MIXER_SAMPLE_RATE: u32 = 44100;
+
+/// Sample rate used for synthesized tones and sweeps; there's no input
+/// device or file to take one from.
+const TONE_SAMPLE_RATE: u32 = 44100;
+
+/// Gain every source is currently registered with. `AudioMixer` applies this
+/// to both its shared playback mix and its visualization mixdown, so the two
+/// stay in sync.
+const DEFAULT_GAIN: f32 = 1.0;
+
+/// Parses `--tone`'s argument, e.g. `sine:440` or `square:220`.
+fn parse_tone_arg(spec: &str) -> Waveform {
+    let (kind, hz) = spec.split_once(':').expect("--tone expects e.g. sine:440");
+    let tone_hz: f32 = hz.parse().expect("invalid tone frequency");
+    match kind {
+        "sine" => Waveform::Sine { tone_hz },
+        "square" => Waveform::Square { tone_hz },
+        other => panic!("unknown tone waveform: {} (expected sine or square)", other),
+    }
+}
+
+/// Parses `--sweep`'s argument, e.g. `50:8000:10s`.
+fn parse_sweep_arg(spec: &str) -> Waveform {
+    let mut parts = spec.split(':');
+    let mut next_f32 = |what: &str| -> f32 {
+        parts.next()
+            .unwrap_or_else(|| panic!("--sweep expects start:end:duration, missing {}", what))
+            .trim_end_matches('s')
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid {} in --sweep argument", what))
     };
+    let start_hz = next_f32("start frequency");
+    let end_hz = next_f32("end frequency");
+    let duration_secs = next_f32("duration");
+    Waveform::Sweep {
+        start_hz,
+        end_hz,
+        duration: Duration::from_secs_f32(duration_secs),
+    }
+}
+
+/// Registers a synthesized tone/sweep source with `mixer`, which plays it
+/// through its shared output sink alongside every other registered source.
+/// The tone generator is always mono.
+fn add_tone_source(mixer: &mut AudioMixer, waveform: Waveform, denoise_enabled: bool) -> (PlaybackHandle, usize) {
+    println!("sample_rate: {}", TONE_SAMPLE_RATE);
+
+    let tone = ToneSource::new(waveform, TONE_SAMPLE_RATE);
+    let pcm = Arc::new(Mutex::new(PcmBuffers::new()));
+    let tapped = SourceTap::new(tone, pcm.clone());
+
+    let id = mixer.add_source(Some(Box::new(tapped)), pcm, TONE_SAMPLE_RATE, DEFAULT_GAIN, denoise_enabled.then(|| Denoiser::new(1)));
+    (PlaybackHandle::Mixed, id)
+}
+
+/// Registers a decoded file as a source with `mixer`, which plays it through
+/// its shared output sink alongside every other registered source. With the
+/// `ffmpeg` feature enabled this decodes through `ffmpeg_next` (covering
+/// formats `rodio::Decoder` can't, and streaming rather than buffering the
+/// whole file), falling back to rodio's own decoder if ffmpeg can't open the
+/// file or the feature is disabled.
+#[cfg(feature = "ffmpeg")]
+fn add_file_source(mixer: &mut AudioMixer, audio_file_name: String, denoise_enabled: bool) -> (PlaybackHandle, usize) {
+    match FfmpegSource::open(&audio_file_name, MIXER_SAMPLE_RATE) {
+        Ok(source) => {
+            println!("Decoding {} via ffmpeg", audio_file_name);
+
+            let pcm = Arc::new(Mutex::new(PcmBuffers::new()));
+            let tapped = SourceTap::new(source, pcm.clone());
+
+            let id = mixer.add_source(Some(Box::new(tapped)), pcm, MIXER_SAMPLE_RATE, DEFAULT_GAIN, denoise_enabled.then(|| Denoiser::new(1)));
+            (PlaybackHandle::Mixed, id)
+        },
+        Err(err) => {
+            eprintln!("ffmpeg couldn't open {} ({}), falling back to rodio", audio_file_name, err);
+            add_file_source_rodio(mixer, audio_file_name, denoise_enabled)
+        },
+    }
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn add_file_source(mixer: &mut AudioMixer, audio_file_name: String, denoise_enabled: bool) -> (PlaybackHandle, usize) {
+    add_file_source_rodio(mixer, audio_file_name, denoise_enabled)
+}
+
+fn add_file_source_rodio(mixer: &mut AudioMixer, audio_file_name: String, denoise_enabled: bool) -> (PlaybackHandle, usize) {
     let audio_file = std::fs::File::open(audio_file_name).expect("Failed to open audio file");
 
+    let source = rodio::Decoder::new(audio_file).unwrap().delay(Duration::from_secs(1));
+    let source = source.buffered();
+    let sample_rate = source.sample_rate();
+    let channels = source.channels();
+    println!("sample_rate: {}", sample_rate);
+    println!("channels: {}", channels);
+
+    let pcm = Arc::new(Mutex::new(PcmBuffers::new()));
+    let tapped = SourceTap::new(source, pcm.clone());
+
+    // SourceTap downmixes to mono before it ever reaches `pcm`, so the
+    // denoiser always sees a single channel here regardless of the file's own.
+    let id = mixer.add_source(Some(Box::new(tapped)), pcm, sample_rate, DEFAULT_GAIN, denoise_enabled.then(|| Denoiser::new(1)));
+    (PlaybackHandle::Mixed, id)
+}
+
+/// Registers the default live input device as a source with `mixer`. Live
+/// capture is never added to the mixer's playback mix (that would feed the
+/// microphone back out of the speakers), so its gain only affects the
+/// visualization mixdown.
+fn add_live_source(mixer: &mut AudioMixer, denoise_enabled: bool) -> (PlaybackHandle, usize) {
+    let (stream, pcm, sample_rate) = start_live_capture();
+    println!("Live input sample_rate: {}", sample_rate);
+
+    let id = mixer.add_source(None, pcm, sample_rate, DEFAULT_GAIN, denoise_enabled.then(|| Denoiser::new(1)));
+    (PlaybackHandle::Live(stream), id)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `--denoise` and `--vad-threshold` are modifiers, not mode selectors, so
+    // they're scanned for independent of which input mode is active.
+    let denoise_enabled = args.iter().any(|a| a == "--denoise");
+    let vad_threshold: f32 = args.iter()
+        .position(|a| a == "--vad-threshold")
+        .and_then(|i| args.get(i + 1))
+        .map(|v| v.parse().expect("invalid --vad-threshold value"))
+        .unwrap_or(0.0);
+
+    // Strip the modifier flags (and --vad-threshold's value) out before mode
+    // selection below, so e.g. `--denoise --live` or `--vad-threshold 0.5
+    // input.mp3` doesn't mistake a leading flag for the file name.
+    let mut mode_args: Vec<&str> = Vec::new();
+    let mut skip_next = false;
+    for arg in &args {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match arg.as_str() {
+            "--denoise" => continue,
+            "--vad-threshold" => skip_next = true,
+            other => mode_args.push(other),
+        }
+    }
+
+    let mut mixer = AudioMixer::new(MIXER_SAMPLE_RATE, SAMPLE_COUNT);
+    // Keeps every registered source's playback alive, alongside the mixer id
+    // it was registered under so a later keypress can unregister it again.
+    let mut playback_handles: Vec<(usize, PlaybackHandle)> = Vec::new();
+
+    let initial_source = match mode_args.first().copied() {
+        Some("--live") => add_live_source(&mut mixer, denoise_enabled),
+        Some("--tone") => {
+            let spec = mode_args.get(1).expect("--tone requires an argument, e.g. sine:440");
+            add_tone_source(&mut mixer, parse_tone_arg(spec), denoise_enabled)
+        },
+        Some("--sweep") => {
+            let spec = mode_args.get(1).expect("--sweep requires an argument, e.g. 50:8000:10s");
+            add_tone_source(&mut mixer, parse_sweep_arg(spec), denoise_enabled)
+        },
+        audio_file_name => {
+            let audio_file_name = match audio_file_name {
+                Some(name) => name.to_string(),
+                None => {
+                    println!("Defaulting to ./input.mp3");
+                    "input.mp3".to_string()
+                },
+            };
+            add_file_source(&mut mixer, audio_file_name, denoise_enabled)
+        },
+    };
+    playback_handles.push((initial_source.1, initial_source.0));
+
     let event_loop = winit::event_loop::EventLoop::new();
     let window = winit::window::WindowBuilder::new()
         .with_title("Sound Galaxy")
@@ -120,20 +312,6 @@ fn main() {
     let mut resolution_buffer = ResolutionBuffer::new(renderer.device());
     let mut ellipse_renderer = EllipseRenderer::new(renderer.device(), renderer.config().format, &resolution_buffer, PARTICLE_COUNT as wgpu::BufferAddress);
 
-    let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-    let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-    let source = rodio::Decoder::new(audio_file).unwrap().delay(std::time::Duration::from_secs(1));
-    let source = source.buffered();
-    let samples: Vec<f32> = source.clone().map(|input| -> _ {
-        input as f32
-    }).collect();
-    println!("samples count: {}", samples.len());
-    let sample_rate = source.sample_rate();
-    println!("sample_rate: {}", sample_rate);
-    println!("channels: {}", source.channels());
-    sink.pause();
-    sink.append(source);
-    
     let mut particles = Vec::with_capacity(PARTICLE_COUNT);
     for i in 0..PARTICLE_COUNT {
         let x = if i < PARTICLE_COUNT/2 {
@@ -144,12 +322,10 @@ fn main() {
         particles.push(Particle::new(x));
     }
 
-    let mut constrainer = Constrainer::new(window.inner_size().width as f32, window.inner_size().height as f32, particles, &mut ellipse_renderer, &renderer, &mut resolution_buffer);
+    let mut constrainer = Constrainer::new(window.inner_size().width as f32, window.inner_size().height as f32, particles, vad_threshold, &mut ellipse_renderer, &renderer, &mut resolution_buffer);
     
-    let timer = Instant::now();
     let mut last_frame_time = Instant::now();
     let mut deltatime = 0.0;
-    sink.play();
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
 
@@ -188,6 +364,24 @@ fn main() {
                             &mut resolution_buffer,
                         )
                     },
+                    WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+                        match input.virtual_keycode {
+                            // Add a 440Hz sine tone source to the live mix.
+                            Some(VirtualKeyCode::T) => {
+                                let (handle, id) = add_tone_source(&mut mixer, Waveform::Sine { tone_hz: 440.0 }, denoise_enabled);
+                                playback_handles.push((id, handle));
+                                println!("Added tone source ({} active)", mixer.source_count());
+                            },
+                            // Drop the most recently added source.
+                            Some(VirtualKeyCode::Back) => {
+                                if let Some((id, _handle)) = playback_handles.pop() {
+                                    mixer.remove_source(id);
+                                    println!("Removed a source ({} active)", mixer.source_count());
+                                }
+                            },
+                            _ => {},
+                        }
+                    },
                     _ => {}
                 }
             },
@@ -198,19 +392,16 @@ fn main() {
                         let mut encoder = renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
                             label: Some("Render encoder"),
                         });
-                        let current_sample = (timer.elapsed().as_secs_f32()*2.0*sample_rate as f32) as usize;
-                        if current_sample >= samples.len() {
-                            println!("Ran out of samples. Exiting...");
-                            *control_flow = ControlFlow::Exit;
-                        } else if current_sample > SAMPLE_COUNT {
-                            let current_samples = &samples[(current_sample-SAMPLE_COUNT)..current_sample];
+                        mixer.tick();
+                        // No source has buffered anything yet; just skip the FFT.
+                        if let Some(mixdown) = mixer.mixdown() {
                             let spectrum = samples_fft_to_spectrum(
-                                current_samples,
-                                sample_rate,
+                                &mixdown,
+                                mixer.sample_rate(),
                                 FrequencyLimit::All,
                                 Some(&spectrum_analyzer::scaling::divide_by_N),
                             ).unwrap();
-                            constrainer.set_particles_with_spectrum_and_deltatime(spectrum, deltatime, &mut ellipse_renderer, &renderer);
+                            constrainer.set_particles_with_spectrum_and_deltatime(spectrum, deltatime, mixer.vad_probability(), &mut ellipse_renderer, &renderer);
                         }
                         ellipse_renderer.render_all(&mut encoder, &view, wgpu::LoadOp::Clear(wgpu::Color {
                             r: 0.0,
@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::{Denoiser, PcmBuffers};
+
+/// Wraps a playback source so `AudioMixer::remove_source` can make it end
+/// early: `rodio::dynamic_mixer` has no API to remove an input directly, but
+/// it does drop one from the mix as soon as it yields `None`. Flagging
+/// `stopped` makes that happen on the input's next pull instead of waiting
+/// for it to run out on its own (tones and live input never would).
+struct Stoppable<S> {
+    inner: S,
+    stopped: Arc<AtomicBool>,
+}
+
+impl<S: Iterator<Item = i16>> Iterator for Stoppable<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.inner.next()
+    }
+}
+
+impl<S: Source<Item = i16>> Source for Stoppable<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+/// One registered input to an `AudioMixer`: its own tapped `PcmBuffers` (fed
+/// by whatever's actually playing it — the mixer's shared sink or a cpal
+/// capture callback), resampled and windowed to the mixer's common rate, and
+/// the flag that stops its contribution to the shared playback mix (if any)
+/// once it's unregistered.
+struct MixerSource {
+    pcm: Arc<Mutex<PcmBuffers>>,
+    sample_rate: u32,
+    gain: f32,
+    window: VecDeque<f32>,
+    denoiser: Option<Denoiser>,
+    last_vad_probability: f32,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Combines several concurrent sources (files, the tone generator, or live
+/// input) into both a single mixed playback stream and a mono mixdown
+/// window for the FFT. Playback mixing is delegated to
+/// `rodio::dynamic_mixer`, which gain-scales (via `Source::amplify`) and
+/// sums every registered source's audio onto one shared output sink, so the
+/// actual blend the listener hears is produced in software here rather than
+/// left to the OS mixing several simultaneous output streams. Visualization
+/// mixing is separate: each source is also tapped into its own `PcmBuffers`,
+/// which this mixer resamples and windows down to a common rate for the FFT.
+/// Sources are stored by slot so they can be registered or unregistered at
+/// runtime (e.g. on a keypress) without disturbing the others' ids.
+pub struct AudioMixer {
+    sample_rate: u32,
+    window_len: usize,
+    sources: Vec<Option<MixerSource>>,
+    playback_controller: Arc<rodio::dynamic_mixer::DynamicMixerController<i16>>,
+    _playback_sink: rodio::Sink,
+    _playback_stream: rodio::OutputStream,
+}
+
+impl AudioMixer {
+    pub fn new(sample_rate: u32, window_len: usize) -> Self {
+        let (playback_controller, playback_output) = rodio::dynamic_mixer::mixer::<i16>(2, sample_rate);
+
+        let (playback_stream, playback_stream_handle) = rodio::OutputStream::try_default()
+            .expect("Failed to open default output device");
+        let playback_sink = rodio::Sink::try_new(&playback_stream_handle)
+            .expect("Failed to create output sink");
+        playback_sink.append(playback_output);
+        playback_sink.play();
+
+        Self {
+            sample_rate,
+            window_len,
+            sources: Vec::new(),
+            playback_controller,
+            _playback_sink: playback_sink,
+            _playback_stream: playback_stream,
+        }
+    }
+
+    /// Registers a new source and returns the id it can later be removed by.
+    /// `playback`, if given, is gain-scaled and added to the shared output
+    /// mix (live input passes `None` here, since playing captured audio back
+    /// out would just feed back into the microphone).
+    pub fn add_source(
+        &mut self,
+        playback: Option<Box<dyn Source<Item = i16> + Send>>,
+        pcm: Arc<Mutex<PcmBuffers>>,
+        sample_rate: u32,
+        gain: f32,
+        denoiser: Option<Denoiser>,
+    ) -> usize {
+        let stopped = Arc::new(AtomicBool::new(false));
+        if let Some(playback) = playback {
+            self.playback_controller.add(Stoppable {
+                inner: playback.amplify(gain),
+                stopped: stopped.clone(),
+            });
+        }
+
+        let source = MixerSource {
+            pcm,
+            sample_rate,
+            gain,
+            window: VecDeque::with_capacity(self.window_len),
+            denoiser,
+            last_vad_probability: 1.0,
+            stopped,
+        };
+        if let Some(slot) = self.sources.iter().position(Option::is_none) {
+            self.sources[slot] = Some(source);
+            slot
+        } else {
+            self.sources.push(Some(source));
+            self.sources.len() - 1
+        }
+    }
+
+    pub fn remove_source(&mut self, id: usize) {
+        if let Some(slot) = self.sources.get_mut(id) {
+            if let Some(source) = slot.take() {
+                source.stopped.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn source_count(&self) -> usize {
+        self.sources.iter().filter(|s| s.is_some()).count()
+    }
+
+    /// Drains whatever each source's tap has produced since the last tick,
+    /// denoises and resamples it to the mixer's common rate, and folds it
+    /// into that source's rolling window.
+    pub fn tick(&mut self) {
+        for slot in &mut self.sources {
+            let Some(source) = slot else { continue };
+
+            let available = source.pcm.lock().unwrap().samples_available();
+            if available == 0 {
+                continue;
+            }
+            let mut chunk = vec![0.0; available];
+            source.pcm.lock().unwrap().consume_exact(&mut chunk);
+
+            let chunk = match &mut source.denoiser {
+                Some(denoiser) => {
+                    let (cleaned, vad) = denoiser.process(&chunk);
+                    source.last_vad_probability = vad;
+                    cleaned
+                },
+                None => chunk,
+            };
+
+            let resampled = resample_linear(&chunk, source.sample_rate, self.sample_rate);
+            source.window.extend(resampled);
+            while source.window.len() > self.window_len {
+                source.window.pop_front();
+            }
+        }
+    }
+
+    /// Sums the latest `window_len` samples across all registered sources,
+    /// scaled by each source's gain and clamped to avoid clipping. A source
+    /// that hasn't buffered a full window yet (buffer underrun, e.g. it was
+    /// just added) contributes silence for its missing leading samples
+    /// instead of holding up the rest of the mix. Returns `None` if no
+    /// source has produced anything yet.
+    pub fn mixdown(&self) -> Option<Vec<f32>> {
+        if self.sources.iter().flatten().all(|s| s.window.is_empty()) {
+            return None;
+        }
+
+        let mut mixed = vec![0.0f32; self.window_len];
+        for source in self.sources.iter().flatten() {
+            let start = self.window_len.saturating_sub(source.window.len());
+            for (i, sample) in source.window.iter().enumerate() {
+                mixed[start + i] += sample * source.gain;
+            }
+        }
+        for sample in &mut mixed {
+            *sample = sample.clamp(-32768.0, 32767.0);
+        }
+        Some(mixed)
+    }
+
+    /// The lowest voice-activity probability among sources that are
+    /// denoising, or `1.0` if none are (so a default `vad_threshold` of 0.0
+    /// never gates anything).
+    pub fn vad_probability(&self) -> f32 {
+        self.sources.iter()
+            .flatten()
+            .filter(|s| s.denoiser.is_some())
+            .map(|s| s.last_vad_probability)
+            .fold(1.0, f32::min)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Linearly resamples `samples` from `from_rate` to `to_rate`. A no-op when
+/// the rates already match.
+fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = to_rate as f64 / from_rate as f64;
+    let out_len = (samples.len() as f64 * ratio) as usize;
+    let mut out = Vec::with_capacity(out_len);
+    for i in 0..out_len {
+        let src_pos = i as f64 / ratio;
+        let index = src_pos.floor() as usize;
+        let frac = (src_pos - index as f64) as f32;
+        let a = samples[index.min(samples.len() - 1)];
+        let b = samples[(index + 1).min(samples.len() - 1)];
+        out.push(a + (b - a) * frac);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_linear_is_noop_when_rates_match() {
+        let samples = vec![1.0, 2.0, 3.0, 4.0];
+        assert_eq!(resample_linear(&samples, 44100, 44100), samples);
+    }
+
+    #[test]
+    fn resample_linear_upsamples_by_interpolating() {
+        let samples = vec![0.0, 10.0];
+        let out = resample_linear(&samples, 1, 2);
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0], 0.0);
+        assert_eq!(out[2], 10.0);
+    }
+
+    #[test]
+    fn resample_linear_downsamples_by_dropping_samples() {
+        let samples = vec![0.0, 1.0, 2.0, 3.0];
+        let out = resample_linear(&samples, 4, 2);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], 0.0);
+        assert_eq!(out[1], 2.0);
+    }
+}
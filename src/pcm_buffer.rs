@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::Source;
+
+/// A producer/consumer PCM ring buffer: decoded audio is pushed in as whole
+/// chunks by `produce`, and the render loop pulls exact-sized windows back
+/// out with `consume_exact`. Because the consumer only ever sees samples the
+/// producer has actually handed over, this gives sample-accurate sync
+/// between what's visualized and what's been decoded/played, instead of
+/// guessing a playback position from a wall-clock timer.
+#[derive(Debug, Default)]
+pub struct PcmBuffers {
+    buffers: Vec<Vec<f32>>,
+    consumer_cursor: usize,
+}
+
+impl PcmBuffers {
+    pub fn new() -> Self {
+        Self {
+            buffers: Vec::new(),
+            consumer_cursor: 0,
+        }
+    }
+
+    /// Appends a decoded chunk to the back of the queue.
+    pub fn produce(&mut self, chunk: Vec<f32>) {
+        if !chunk.is_empty() {
+            self.buffers.push(chunk);
+        }
+    }
+
+    /// Total samples waiting to be consumed.
+    pub fn samples_available(&self) -> usize {
+        self.buffers.iter().map(Vec::len).sum::<usize>() - self.consumer_cursor
+    }
+
+    /// Copies exactly `data.len()` samples out of the queue, advancing the
+    /// consumer cursor and dropping any front buffers it exhausts. Returns
+    /// `false` (leaving the queue untouched) if fewer samples are available
+    /// than requested.
+    pub fn consume_exact(&mut self, data: &mut [f32]) -> bool {
+        if self.samples_available() < data.len() {
+            return false;
+        }
+
+        let mut written = 0;
+        while written < data.len() {
+            let front = &self.buffers[0];
+            let available_in_front = front.len() - self.consumer_cursor;
+            let take = available_in_front.min(data.len() - written);
+            data[written..written + take]
+                .copy_from_slice(&front[self.consumer_cursor..self.consumer_cursor + take]);
+            written += take;
+            self.consumer_cursor += take;
+
+            if self.consumer_cursor >= front.len() {
+                self.buffers.remove(0);
+                self.consumer_cursor = 0;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consume_exact_fails_without_touching_the_queue_when_short() {
+        let mut pcm = PcmBuffers::new();
+        pcm.produce(vec![1.0, 2.0]);
+
+        let mut out = [0.0; 3];
+        assert!(!pcm.consume_exact(&mut out));
+        assert_eq!(pcm.samples_available(), 2);
+    }
+
+    #[test]
+    fn consume_exact_spans_and_drops_exhausted_front_buffers() {
+        let mut pcm = PcmBuffers::new();
+        pcm.produce(vec![1.0, 2.0]);
+        pcm.produce(vec![3.0, 4.0, 5.0]);
+
+        let mut out = [0.0; 4];
+        assert!(pcm.consume_exact(&mut out));
+        assert_eq!(out, [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(pcm.samples_available(), 1);
+
+        let mut out = [0.0; 1];
+        assert!(pcm.consume_exact(&mut out));
+        assert_eq!(out, [5.0]);
+        assert_eq!(pcm.samples_available(), 0);
+    }
+
+    #[test]
+    fn produce_ignores_empty_chunks() {
+        let mut pcm = PcmBuffers::new();
+        pcm.produce(vec![]);
+        assert_eq!(pcm.samples_available(), 0);
+    }
+}
+
+/// How many samples `SourceTap` batches up before handing them to the
+/// `PcmBuffers` it feeds, so the mutex isn't taken once per sample.
+const TAP_FLUSH_SIZE: usize = 1024;
+
+/// Wraps a rodio `Source` and mirrors every sample it yields into a shared
+/// `PcmBuffers` as it's played, so the visualizer's consumer sees audio at
+/// the same offset rodio's sink is actually emitting. What's mirrored into
+/// `pcm` is always downmixed to mono (averaging each frame's channels),
+/// matching the live-capture path, even though `Source::channels` still
+/// reports the inner source's real channel count for playback.
+pub struct SourceTap<S> {
+    inner: S,
+    pcm: Arc<Mutex<PcmBuffers>>,
+    channels: usize,
+    pending: Vec<f32>,
+}
+
+impl<S: Source<Item = i16>> SourceTap<S> {
+    pub fn new(inner: S, pcm: Arc<Mutex<PcmBuffers>>) -> Self {
+        let channels = inner.channels() as usize;
+        Self {
+            inner,
+            pcm,
+            channels,
+            pending: Vec::with_capacity(TAP_FLUSH_SIZE),
+        }
+    }
+
+    /// Downmixes and hands off every complete frame currently in `pending`,
+    /// carrying any leftover partial frame over to the next flush.
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        if self.channels <= 1 {
+            self.pcm.lock().unwrap().produce(std::mem::take(&mut self.pending));
+            return;
+        }
+
+        let full_frames = self.pending.len() / self.channels;
+        if full_frames == 0 {
+            return;
+        }
+        let take = full_frames * self.channels;
+        let mono: Vec<f32> = self.pending[..take]
+            .chunks(self.channels)
+            .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+            .collect();
+        self.pending.drain(0..take);
+        self.pcm.lock().unwrap().produce(mono);
+    }
+}
+
+impl<S: Iterator<Item = i16>> Iterator for SourceTap<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        match self.inner.next() {
+            Some(sample) => {
+                self.pending.push(sample as f32);
+                if self.pending.len() >= TAP_FLUSH_SIZE {
+                    self.flush();
+                }
+                Some(sample)
+            },
+            None => {
+                self.flush();
+                None
+            },
+        }
+    }
+}
+
+impl<S: Source<Item = i16>> Source for SourceTap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+const TAU: f32 = std::f32::consts::TAU;
+const VOLUME: i16 = i16::MAX / 4;
+
+/// Which waveform a `ToneSource` generates.
+pub enum Waveform {
+    Sine { tone_hz: f32 },
+    Square { tone_hz: f32 },
+    /// Linearly sweeps a sine tone from `start_hz` to `end_hz` over `duration`,
+    /// then holds at `end_hz`.
+    Sweep {
+        start_hz: f32,
+        end_hz: f32,
+        duration: Duration,
+    },
+}
+
+/// A synthesized `rodio::Source` with no input file, useful for testing the
+/// particle mapping against known frequencies and for demos. Because the
+/// exact frequencies are known ahead of time, it gives a deterministic way to
+/// confirm which particle columns light up for which FFT bins.
+pub struct ToneSource {
+    waveform: Waveform,
+    sample_rate: u32,
+    running_sample_index: u64,
+    phase: f32,
+}
+
+impl ToneSource {
+    pub fn new(waveform: Waveform, sample_rate: u32) -> Self {
+        Self {
+            waveform,
+            sample_rate,
+            running_sample_index: 0,
+            phase: 0.0,
+        }
+    }
+
+    /// The instantaneous tone frequency, accounting for sweep progress.
+    fn current_tone_hz(&self) -> f32 {
+        match self.waveform {
+            Waveform::Sine { tone_hz } | Waveform::Square { tone_hz } => tone_hz,
+            Waveform::Sweep { start_hz, end_hz, duration } => {
+                let elapsed = self.running_sample_index as f32 / self.sample_rate as f32;
+                let t = (elapsed / duration.as_secs_f32()).min(1.0);
+                start_hz + (end_hz - start_hz) * t
+            },
+        }
+    }
+}
+
+impl Iterator for ToneSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let tone_hz = self.current_tone_hz();
+
+        let sample = match self.waveform {
+            Waveform::Square { .. } => {
+                let wave_period = (self.sample_rate as f32 / tone_hz).max(1.0) as u64;
+                let half_period = (wave_period / 2).max(1);
+                if (self.running_sample_index / half_period) % 2 == 0 {
+                    VOLUME
+                } else {
+                    -VOLUME
+                }
+            },
+            Waveform::Sine { .. } | Waveform::Sweep { .. } => {
+                self.phase += TAU * tone_hz / self.sample_rate as f32;
+                (VOLUME as f32 * self.phase.sin()) as i16
+            },
+        };
+
+        self.running_sample_index += 1;
+        Some(sample)
+    }
+}
+
+impl Source for ToneSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self.waveform {
+            Waveform::Sweep { duration, .. } => Some(duration),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_toggles_every_half_period() {
+        let mut tone = ToneSource::new(Waveform::Square { tone_hz: 2.0 }, 8);
+        let samples: Vec<i16> = (0..6).map(|_| tone.next().unwrap()).collect();
+        assert_eq!(samples, [VOLUME, VOLUME, -VOLUME, -VOLUME, VOLUME, VOLUME]);
+    }
+
+    #[test]
+    fn sine_wave_matches_known_samples_at_quarter_turns() {
+        let mut tone = ToneSource::new(Waveform::Sine { tone_hz: 1.0 }, 4);
+        let samples: Vec<i16> = (0..4).map(|_| tone.next().unwrap()).collect();
+        // phase advances by TAU/4 each sample: pi/2, pi, 3pi/2, 2pi.
+        assert_eq!(samples[0], VOLUME);
+        assert!(samples[1].abs() <= 1);
+        assert_eq!(samples[2], -VOLUME);
+        assert!(samples[3].abs() <= 1);
+    }
+
+    #[test]
+    fn sweep_reports_its_total_duration() {
+        let duration = Duration::from_secs(5);
+        let tone = ToneSource::new(Waveform::Sweep { start_hz: 50.0, end_hz: 8000.0, duration }, 44100);
+        assert_eq!(tone.total_duration(), Some(duration));
+    }
+
+    #[test]
+    fn sine_reports_no_total_duration() {
+        let tone = ToneSource::new(Waveform::Sine { tone_hz: 440.0 }, 44100);
+        assert_eq!(tone.total_duration(), None);
+    }
+}